@@ -1,12 +1,18 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::{Parser, ValueEnum};
 use dirs::home_dir;
 
 use anyhow::Result;
+use russh_keys::agent::client::AgentClient;
 use russh_keys::key::KeyPair;
 use russh_keys::load_secret_key;
 use serde::{Serialize, Serializer};
+use zeroize::Zeroizing;
+
+use crate::forward::{parse_local_forward, parse_remote_forward, Forward};
+use crate::ssh::Authenticator;
+use crate::transfer::{parse_pull, parse_push, Transfer};
 
 #[derive(clap::Parser)]
 struct Cli {
@@ -16,53 +22,141 @@ struct Cli {
     #[clap(long, short = 'k')]
     private_key: Option<PathBuf>,
 
+    #[clap(long = "identity", short = 'i')]
+    identities: Vec<PathBuf>,
+
     #[clap(long, short = 'p')]
     sudo_prompt_password: bool,
 
+    #[clap(long, short = 's')]
+    shell: bool,
+
+    #[clap(long = "local-forward", short = 'L', value_parser = parse_local_forward)]
+    local_forwards: Vec<Forward>,
+
+    #[clap(long = "remote-forward", short = 'R', value_parser = parse_remote_forward)]
+    remote_forwards: Vec<Forward>,
+
+    #[clap(long = "push", value_parser = parse_push)]
+    pushes: Vec<Transfer>,
+
+    #[clap(long = "pull", value_parser = parse_pull)]
+    pulls: Vec<Transfer>,
+
     #[arg(num_args=1..)]
     #[clap(value_parser = parse_host_login)]
     hosts: Vec<RemoteHost>,
 
     #[clap(long, short, default_value = "table")]
     output: Output,
+
+    #[clap(long, default_value = "accept-new")]
+    strict_host_key_checking: StrictHostKeyChecking,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub enum StrictHostKeyChecking {
+    Yes,
+    No,
+    AcceptNew,
 }
 
 #[derive(ValueEnum, Clone)]
 #[clap(rename_all = "kebab_case")]
 pub enum Output {
     Json,
+    Ndjson,
     Text,
     Table,
 }
 
 pub struct Args {
     pub commands: Vec<String>,
-    pub key_pair: KeyPair,
+    pub authenticators: Vec<Authenticator>,
     pub hosts: Vec<RemoteHost>,
     pub output: Output,
-    pub sudo_prompt_password: bool,
+    pub sudo_password: Option<Zeroizing<String>>,
+    pub shell: bool,
+    pub forwards: Vec<Forward>,
+    pub transfers: Vec<Transfer>,
+    pub strict_host_key_checking: StrictHostKeyChecking,
 }
 
-pub fn cli() -> Result<Args> {
+pub async fn cli() -> Result<Args> {
     let cli = Cli::parse();
 
     let home = home_dir().ok_or_else(|| anyhow::anyhow!("No home directory"))?;
-    let private_key_path = cli.private_key.unwrap_or_else(|| {
-        let mut path = home;
-        path.push(".ssh/id_ed25519");
-        path
-    });
+    let authenticators = authenticators(&cli, &home).await?;
+
+    let mut forwards = cli.local_forwards;
+    forwards.extend(cli.remote_forwards);
+
+    let mut transfers = cli.pushes;
+    transfers.extend(cli.pulls);
+
+    let sudo_password = if cli.sudo_prompt_password {
+        Some(Zeroizing::new(rpassword::prompt_password("sudo password: ")?))
+    } else {
+        None
+    };
 
-    let key_pair = load_secret_key(private_key_path, None)?;
     Ok(Args {
-        sudo_prompt_password: cli.sudo_prompt_password,
+        sudo_password,
+        shell: cli.shell,
         commands: cli.commands,
-        key_pair,
+        authenticators,
         hosts: cli.hosts,
         output: cli.output,
+        forwards,
+        transfers,
+        strict_host_key_checking: cli.strict_host_key_checking,
     })
 }
 
+/// Build the ordered list of credentials to offer: agent identities first, then
+/// any keys named with `-i`/`-k`, falling back to the usual `~/.ssh` key files.
+async fn authenticators(cli: &Cli, home: &Path) -> Result<Vec<Authenticator>> {
+    let mut authenticators = Vec::new();
+
+    if let Ok(mut agent) = AgentClient::connect_env().await {
+        if let Ok(identities) = agent.request_identities().await {
+            authenticators.extend(identities.into_iter().map(Authenticator::Agent));
+        }
+    }
+
+    let mut paths = cli.identities.clone();
+    if let Some(private_key) = &cli.private_key {
+        paths.push(private_key.clone());
+    }
+    if paths.is_empty() {
+        for name in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+            let path = home.join(".ssh").join(name);
+            if path.exists() {
+                paths.push(path);
+            }
+        }
+    }
+
+    for path in paths {
+        authenticators.push(Authenticator::Key(load_key(&path)?));
+    }
+
+    Ok(authenticators)
+}
+
+fn load_key(path: &Path) -> Result<KeyPair> {
+    match load_secret_key(path, None) {
+        Ok(key) => Ok(key),
+        Err(russh_keys::Error::KeyIsEncrypted) => {
+            let prompt = format!("Enter passphrase for {}: ", path.display());
+            let passphrase = rpassword::prompt_password(prompt)?;
+            Ok(load_secret_key(path, Some(&passphrase))?)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct RemoteHost {
     pub host: String,
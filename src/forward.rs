@@ -0,0 +1,106 @@
+use anyhow::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardDirection {
+    LocalToRemote,
+    RemoteToLocal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forward {
+    pub direction: ForwardDirection,
+    pub bind_host: String,
+    pub bind_port: u16,
+    pub host: String,
+    pub host_port: u16,
+}
+
+/// SSH channel forwarding is TCP-only, so UDP is out of scope: reject an explicit
+/// `udp:` prefix and strip an optional `tcp:` one, leaving the forward spec itself.
+fn strip_protocol(spec: &str) -> Result<&str> {
+    if spec.strip_prefix("udp:").is_some() {
+        Err(anyhow::anyhow!("UDP forwarding is not supported"))
+    } else if let Some(rest) = spec.strip_prefix("tcp:") {
+        Ok(rest)
+    } else {
+        Ok(spec)
+    }
+}
+
+fn parse_forward(spec: &str, direction: ForwardDirection) -> Result<Forward> {
+    let rest = strip_protocol(spec)?;
+
+    let (bind_host, bind_port, host, host_port) = match rest.split(':').collect::<Vec<_>>().as_slice()
+    {
+        [port, host, host_port] => (
+            "127.0.0.1".to_string(),
+            port.parse()?,
+            host.to_string(),
+            host_port.parse()?,
+        ),
+        [bind, port, host, host_port] => (
+            bind.to_string(),
+            port.parse()?,
+            host.to_string(),
+            host_port.parse()?,
+        ),
+        _ => return Err(anyhow::anyhow!("Invalid forward spec")),
+    };
+
+    Ok(Forward {
+        direction,
+        bind_host,
+        bind_port,
+        host,
+        host_port,
+    })
+}
+
+pub fn parse_local_forward(spec: &str) -> Result<Forward> {
+    parse_forward(spec, ForwardDirection::LocalToRemote)
+}
+
+pub fn parse_remote_forward(spec: &str) -> Result<Forward> {
+    parse_forward(spec, ForwardDirection::RemoteToLocal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_local_forward_without_bind() {
+        let result = parse_local_forward("8080:example.com:80").unwrap();
+        assert_eq!(result.direction, ForwardDirection::LocalToRemote);
+        assert_eq!(result.bind_host, "127.0.0.1");
+        assert_eq!(result.bind_port, 8080);
+        assert_eq!(result.host, "example.com");
+        assert_eq!(result.host_port, 80);
+    }
+
+    #[test]
+    fn test_parse_local_forward_with_bind() {
+        let result = parse_local_forward("0.0.0.0:8080:example.com:80").unwrap();
+        assert_eq!(result.bind_host, "0.0.0.0");
+        assert_eq!(result.bind_port, 8080);
+    }
+
+    #[test]
+    fn test_parse_local_forward_udp_rejected() {
+        assert!(parse_local_forward("udp:53:resolver:53").is_err());
+    }
+
+    #[test]
+    fn test_parse_remote_forward() {
+        let result = parse_remote_forward("9000:localhost:3000").unwrap();
+        assert_eq!(result.direction, ForwardDirection::RemoteToLocal);
+        assert_eq!(result.bind_port, 9000);
+        assert_eq!(result.host, "localhost");
+        assert_eq!(result.host_port, 3000);
+    }
+
+    #[test]
+    fn test_parse_forward_invalid() {
+        assert!(parse_local_forward("8080:example.com").is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::Result;
+use data_encoding::BASE64;
+use hmac::{Hmac, Mac};
+use russh_keys::key::PublicKey;
+use russh_keys::PublicKeyBase64;
+use sha1::Sha1;
+
+type HmacSha1 = Hmac<Sha1>;
+
+pub enum Verdict {
+    Match,
+    Mismatch,
+    Unknown,
+}
+
+enum Pattern {
+    Plain(Vec<String>),
+    Hashed { salt: Vec<u8>, hash: Vec<u8> },
+}
+
+impl Pattern {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Pattern::Plain(hosts) => hosts.iter().any(|h| h == name),
+            Pattern::Hashed { salt, hash } => {
+                let mut mac = match HmacSha1::new_from_slice(salt) {
+                    Ok(mac) => mac,
+                    Err(_) => return false,
+                };
+                mac.update(name.as_bytes());
+                mac.finalize().into_bytes().as_slice() == hash.as_slice()
+            }
+        }
+    }
+}
+
+struct Entry {
+    pattern: Pattern,
+    keytype: String,
+    key: String,
+}
+
+/// Serializes `append` across concurrent host tasks so a new key observed by two
+/// tasks in the same invocation is written once rather than racing to duplicate it.
+fn append_lock() -> &'static Mutex<()> {
+    static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| Mutex::new(()))
+}
+
+pub struct KnownHosts {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl KnownHosts {
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents.lines().filter_map(parse_line).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self { path, entries })
+    }
+
+    pub fn verify(&self, host: &str, port: u16, key: &PublicKey) -> Verdict {
+        let name = host_name(host, port);
+        let keytype = key.name();
+        let presented = key.public_key_base64();
+
+        let mut seen = false;
+        for entry in &self.entries {
+            if !entry.pattern.matches(&name) || entry.keytype != keytype {
+                continue;
+            }
+            seen = true;
+            if entry.key == presented {
+                return Verdict::Match;
+            }
+        }
+
+        if seen {
+            Verdict::Mismatch
+        } else {
+            Verdict::Unknown
+        }
+    }
+
+    pub fn append(&self, host: &str, port: u16, key: &PublicKey) -> Result<()> {
+        let line = format!(
+            "{} {} {}\n",
+            host_name(host, port),
+            key.name(),
+            key.public_key_base64()
+        );
+
+        let _guard = append_lock().lock().unwrap();
+
+        // Re-read under the lock: another task (or a prior connection this run) may
+        // already have recorded the same key since we snapshotted at connect time.
+        if let Ok(contents) = std::fs::read_to_string(&self.path) {
+            if contents.lines().any(|l| l.trim() == line.trim()) {
+                return Ok(());
+            }
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+fn host_name(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+fn parse_line(line: &str) -> Option<Entry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut fields = line.split_whitespace();
+    let hosts = fields.next()?;
+    let keytype = fields.next()?.to_string();
+    let key = fields.next()?.to_string();
+
+    let pattern = if let Some(rest) = hosts.strip_prefix("|1|") {
+        let mut parts = rest.split('|');
+        let salt = BASE64.decode(parts.next()?.as_bytes()).ok()?;
+        let hash = BASE64.decode(parts.next()?.as_bytes()).ok()?;
+        Pattern::Hashed { salt, hash }
+    } else {
+        Pattern::Plain(hosts.split(',').map(|s| s.to_string()).collect())
+    };
+
+    Some(Entry {
+        pattern,
+        keytype,
+        key,
+    })
+}
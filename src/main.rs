@@ -1,14 +1,17 @@
 mod cli;
 
+mod forward;
+mod known_hosts;
 mod run;
 mod ssh;
+mod transfer;
 
 use tabled::{settings::Style, Table, Tabled};
 
 use thiserror::Error;
 
 use anyhow::Result;
-use cli::{cli, Output, RemoteHost};
+use cli::{cli, Args, Output, RemoteHost};
 use console::Term;
 use run::run;
 use serde::Serialize;
@@ -21,8 +24,11 @@ pub enum RunError {
     #[error("SSH Connection error: {0}")]
     SshConnectionError(String),
 
+    #[error("Host key verification failed: {0}")]
+    HostKeyMismatch(String),
+
     #[error("SSH Run error: {0}")]
-    SshRunError(String, usize),
+    SshRunError(String, usize, u64),
 
     #[error("SSH error occurred: {0}")]
     SshCloseError(String),
@@ -73,6 +79,52 @@ fn duration_print(duration: &u64) -> String {
     format!("{}ms", duration)
 }
 
+/// One line of NDJSON output, written as soon as a host finishes.
+#[derive(Debug, Serialize)]
+pub struct NdjsonRecord {
+    pub host: String,
+    pub index: usize,
+    pub code: Option<u32>,
+    pub duration: u64,
+    pub output: String,
+    pub success: bool,
+}
+
+impl From<&Responses> for Vec<NdjsonRecord> {
+    fn from(responses: &Responses) -> Self {
+        let host = responses.remote_host.host.clone();
+
+        responses
+            .responses
+            .iter()
+            .map(|response| match response {
+                Ok(res) => NdjsonRecord {
+                    host: host.clone(),
+                    index: res.index,
+                    code: res.code,
+                    duration: res.duration,
+                    output: res.out.clone(),
+                    success: true,
+                },
+                Err(e) => {
+                    let (index, duration) = match e {
+                        RunError::SshRunError(_, i, d) => (*i, *d),
+                        _ => (0, 0),
+                    };
+                    NdjsonRecord {
+                        host: host.clone(),
+                        index,
+                        code: None,
+                        duration,
+                        output: e.to_string(),
+                        success: false,
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
 impl CompactResponse {
     pub fn new(
         remote_host: RemoteHost,
@@ -118,18 +170,51 @@ impl From<&Responses> for Vec<CompactResponse> {
     }
 }
 
+/// Stream NDJSON to stdout, writing and flushing one record per response as each
+/// host's results arrive rather than buffering the whole fleet before printing.
+async fn stream_ndjson(args: Args) -> Result<()> {
+    use std::io::Write;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+    let handle = tokio::spawn(run::stream(args, tx));
+
+    let mut stdout = std::io::stdout();
+    while let Some(responses) = rx.recv().await {
+        let records: Vec<NdjsonRecord> = (&responses).into();
+        for record in records {
+            writeln!(stdout, "{}", serde_json::to_string(&record)?)?;
+            stdout.flush()?;
+        }
+    }
+
+    handle.await??;
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     env_logger::builder()
         .filter_level(log::LevelFilter::Info)
         .init();
-    let args = cli()?;
+    let args = cli().await?;
+
+    if args.shell {
+        let code = run::shell(args).await?;
+        std::process::exit(code as i32);
+    }
+
+    if let Output::Ndjson = args.output {
+        return stream_ndjson(args).await;
+    }
+
     let output = args.output.clone();
     let term = Term::stdout();
     let mut all_responses = run(args).await?;
     all_responses.sort_by(|a, b| a.remote_host.host.cmp(&b.remote_host.host));
 
     match output {
+        Output::Ndjson => unreachable!("ndjson is streamed before aggregation"),
         Output::Json => {
             let compact_responses: Vec<CompactResponse> = all_responses
                 .iter()
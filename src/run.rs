@@ -1,57 +1,217 @@
 use futures::{stream, StreamExt};
 use log::trace;
+use tokio::sync::mpsc::{self, Sender};
 
 use crate::cli::Args;
+use crate::forward::ForwardDirection;
+use crate::transfer::TransferDirection;
 use crate::{ssh, Responses, RunError};
 use crate::{Response, RunResult};
 
+fn millis(start_time: std::time::Instant) -> u64 {
+    let elapsed = start_time.elapsed();
+    elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())
+}
+
+/// Drive an interactive shell against a single host, bypassing the fan-out and
+/// table/json aggregation. Returns the remote shell's exit code.
+pub async fn shell(args: Args) -> RunResult<u32> {
+    let host = match args.hosts.as_slice() {
+        [host] => host.clone(),
+        _ => {
+            return Err(RunError::GeneralError(
+                "--shell requires exactly one host".to_string(),
+            ))
+        }
+    };
+
+    let ssh = ssh::connect(&host, &args.authenticators, args.strict_host_key_checking, &[])
+        .await
+        .map_err(|e| RunError::SshConnectionError(e.to_string()))?;
+
+    ssh.shell()
+        .await
+        .map_err(|e| RunError::SshRunError(e.to_string(), 0, 0))
+}
+
 pub async fn run(args: Args) -> RunResult<Vec<Responses>> {
-    let key_pair = args.key_pair;
+    let (tx, mut rx) = mpsc::channel(64);
+    let handle = tokio::spawn(stream(args, tx));
+
+    let mut ret: Vec<Responses> = vec![];
+    while let Some(responses) = rx.recv().await {
+        ret.push(responses);
+    }
+
+    handle
+        .await
+        .map_err(|e| RunError::GeneralError(e.to_string()))??;
+
+    Ok(ret)
+}
+
+/// Fan out across the fleet, emitting each host's [`Responses`] on `tx` the moment
+/// that host finishes so callers can render results as they arrive.
+pub async fn stream(args: Args, tx: Sender<Responses>) -> RunResult<()> {
+    let authenticators = args.authenticators;
     let commands: Vec<String> = args.commands;
+    let strict = args.strict_host_key_checking;
+    let forwards = args.forwards;
+    let transfers = args.transfers;
+    let sudo_password = args.sudo_password;
+
+    // A local forward binds `bind_host:bind_port` on this machine, which only one
+    // host can own; fanning it out would fail every host after the first with
+    // address-in-use. Require a single host rather than silently binding just one.
+    let has_local_forward = forwards
+        .iter()
+        .any(|f| f.direction == ForwardDirection::LocalToRemote);
+    if has_local_forward && args.hosts.len() > 1 {
+        return Err(RunError::GeneralError(
+            "local forwards (-L) require exactly one host".to_string(),
+        ));
+    }
 
     trace!("connecting to {} hosts", args.hosts.len());
 
     let mut sshs = stream::iter(args.hosts)
         .map(|remote_host| {
-            let key_pair = key_pair.clone();
+            let authenticators = authenticators.clone();
             let commands = commands.clone();
+            let forwards = forwards.clone();
+            let transfers = transfers.clone();
+            let sudo_password = sudo_password.clone();
 
             tokio::spawn(async move {
                 let host = &remote_host.host;
+                let remote_forwards: Vec<_> = forwards
+                    .iter()
+                    .filter(|f| f.direction == ForwardDirection::RemoteToLocal)
+                    .cloned()
+                    .collect();
                 trace!("{:>15}: {:>15}", "Shh connect ", host);
-                let mut ssh = match ssh::connect(&remote_host, &key_pair).await {
+                let mut ssh =
+                    match ssh::connect(&remote_host, &authenticators, strict, &remote_forwards).await
+                    {
                     Ok(ssh) => ssh,
                     Err(e) => {
-                        return (
-                            remote_host,
-                            RunResult::Err(RunError::SshConnectionError(e.to_string())),
-                        )
+                        let err = match e.downcast_ref::<ssh::HostKeyMismatch>() {
+                            Some(mismatch) => RunError::HostKeyMismatch(mismatch.0.clone()),
+                            None => RunError::SshConnectionError(e.to_string()),
+                        };
+                        return (remote_host, RunResult::Err(err));
                     }
                 };
                 trace!("{:>15}: {:>15}", "Shh connected ", host);
 
                 let mut responses: Vec<RunResult<Response>> = Vec::new();
+
+                // Pushes lead, commands follow, pulls trail. Offsetting the command
+                // and pull indices past the pushes keeps the index-based sort below
+                // unambiguous instead of colliding pushes with command index 0.
+                let push_count = transfers
+                    .iter()
+                    .filter(|t| t.direction == TransferDirection::Push)
+                    .count();
+
+                for (p, transfer) in transfers
+                    .iter()
+                    .filter(|t| t.direction == TransferDirection::Push)
+                    .enumerate()
+                {
+                    trace!("{:>15}: {:>15} push {}", "Transfer", host, transfer.remote);
+                    let start = std::time::Instant::now();
+                    match ssh.push(&transfer.local, &transfer.remote).await {
+                        Ok((bytes, duration)) => responses.push(RunResult::Ok(Response {
+                            out: format!("pushed {} ({} bytes)", transfer.remote, bytes),
+                            err: String::new(),
+                            code: Some(0),
+                            duration,
+                            index: p,
+                        })),
+                        Err(e) => responses.push(RunResult::Err(RunError::SshRunError(
+                            e.to_string(),
+                            p,
+                            millis(start),
+                        ))),
+                    }
+                }
+
                 for (i, command) in commands.iter().enumerate() {
+                    let index = push_count + i;
                     trace!("{:>15}: {:>15} {}", "Run command", host, command);
-                    match ssh.call(command, &remote_host.sudo).await {
+                    let start = std::time::Instant::now();
+                    match ssh.call(command, &remote_host.sudo, &sudo_password).await {
                         Ok((out, err, code, duration)) => {
                             responses.push(RunResult::Ok(Response {
                                 out,
                                 code,
                                 err,
                                 duration,
-                                index: i,
+                                index,
                             }));
                         }
                         Err(e) => {
-                            responses.push(RunResult::Err(RunError::SshRunError(e.to_string(), i)));
+                            responses.push(RunResult::Err(RunError::SshRunError(
+                                e.to_string(),
+                                index,
+                                millis(start),
+                            )));
                             break;
                         }
                     }
                 }
 
-                if let Err(e) = ssh.close().await {
-                    responses.push(RunResult::Err(RunError::SshCloseError(e.to_string())));
+                let pull_index = push_count + commands.len();
+                for (n, transfer) in transfers
+                    .iter()
+                    .filter(|t| t.direction == TransferDirection::Pull)
+                    .enumerate()
+                {
+                    let index = pull_index + n;
+                    trace!("{:>15}: {:>15} pull {}", "Transfer", host, transfer.remote);
+                    let start = std::time::Instant::now();
+                    match ssh.pull(&transfer.remote, &transfer.local).await {
+                        Ok((bytes, duration)) => responses.push(RunResult::Ok(Response {
+                            out: format!("pulled {} ({} bytes)", transfer.remote, bytes),
+                            err: String::new(),
+                            code: Some(0),
+                            duration,
+                            index,
+                        })),
+                        Err(e) => responses.push(RunResult::Err(RunError::SshRunError(
+                            e.to_string(),
+                            index,
+                            millis(start),
+                        ))),
+                    }
+                }
+
+                if forwards.is_empty() {
+                    if let Err(e) = ssh.close().await {
+                        responses.push(RunResult::Err(RunError::SshCloseError(e.to_string())));
+                    }
+
+                    return (remote_host, Ok(responses));
+                }
+
+                // Keep the session open and tunnel alongside the command loop.
+                for forward in &remote_forwards {
+                    if let Err(e) = ssh.forward_remote(forward).await {
+                        responses.push(RunResult::Err(RunError::SshRunError(e.to_string(), 0, 0)));
+                    }
+                }
+
+                let locals: Vec<_> = forwards
+                    .iter()
+                    .filter(|f| f.direction == ForwardDirection::LocalToRemote)
+                    .map(|forward| ssh.forward_local(forward))
+                    .collect();
+
+                if locals.is_empty() {
+                    std::future::pending::<()>().await;
+                } else {
+                    let _ = futures::future::join_all(locals).await;
                 }
 
                 (remote_host, Ok(responses))
@@ -59,21 +219,20 @@ pub async fn run(args: Args) -> RunResult<Vec<Responses>> {
         })
         .buffer_unordered(10);
 
-    let mut ret: Vec<Responses> = vec![];
     while let Some(res) = sshs.next().await {
-        match res {
+        let responses = match res {
             Ok((remote_host, Ok(responses))) => {
                 let mut responses = responses;
 
                 responses.sort_by(|a, b| {
                     let a_index = match a {
                         Ok(a) => Some(a.index),
-                        Err(RunError::SshRunError(_, i)) => Some(*i),
+                        Err(RunError::SshRunError(_, i, _)) => Some(*i),
                         _ => None,
                     };
                     let b_index = match b {
                         Ok(b) => Some(b.index),
-                        Err(RunError::SshRunError(_, i)) => Some(*i),
+                        Err(RunError::SshRunError(_, i, _)) => Some(*i),
                         _ => None,
                     };
 
@@ -82,22 +241,24 @@ pub async fn run(args: Args) -> RunResult<Vec<Responses>> {
                         _ => std::cmp::Ordering::Equal,
                     }
                 });
-                ret.push(Responses {
+                Responses {
                     remote_host,
                     responses,
-                });
+                }
             }
 
-            Ok((remote_host, Err(e))) => {
-                ret.push(Responses {
-                    remote_host,
-                    responses: vec![Err(e)],
-                });
-            }
+            Ok((remote_host, Err(e))) => Responses {
+                remote_host,
+                responses: vec![Err(e)],
+            },
 
             Err(e) => return Err(RunError::GeneralError(e.to_string())),
+        };
+
+        if tx.send(responses).await.is_err() {
+            break;
         }
     }
 
-    Ok(ret)
+    Ok(())
 }
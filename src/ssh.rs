@@ -1,20 +1,72 @@
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::Result;
 use async_trait::async_trait;
-use russh::{client, ChannelMsg, Disconnect};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, size};
+use dirs::home_dir;
+use russh::{client, Channel, ChannelMsg, Disconnect, Pty};
+use tokio::io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::signal::unix::{signal, SignalKind};
+use russh_keys::agent::client::AgentClient;
 use russh_keys::key::{KeyPair, PublicKey};
+use russh_sftp::client::SftpSession;
 use tokio::time::timeout;
+use zeroize::{Zeroize, Zeroizing};
 
-use crate::cli::RemoteHost;
+use crate::cli::{RemoteHost, StrictHostKeyChecking};
+use crate::forward::Forward;
+use crate::known_hosts::{KnownHosts, Verdict};
 
-pub async fn connect(remote_host: &RemoteHost, key_pair: &KeyPair) -> Result<Session> {
-    let ssh = Session::connect(remote_host, key_pair.clone()).await?;
+/// A single candidate credential to offer the server. The agent variant holds
+/// only the public half; signing happens in the agent when the key is selected.
+#[derive(Clone)]
+pub enum Authenticator {
+    Agent(PublicKey),
+    Key(KeyPair),
+}
+
+pub async fn connect(
+    remote_host: &RemoteHost,
+    authenticators: &[Authenticator],
+    strict: StrictHostKeyChecking,
+    remote_forwards: &[Forward],
+) -> Result<Session> {
+    let ssh = Session::connect(remote_host, authenticators, strict, remote_forwards).await?;
 
     Ok(ssh)
 }
 
-struct Client {}
+fn millis(start_time: std::time::Instant) -> u64 {
+    let elapsed = start_time.elapsed();
+    elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())
+}
+
+/// A rejected host key, surfaced separately so the run module can render it as a
+/// distinct [`crate::RunError`] variant rather than a generic connection error.
+#[derive(Debug)]
+pub struct HostKeyMismatch(pub String);
+
+impl std::fmt::Display for HostKeyMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for HostKeyMismatch {}
+
+struct Client {
+    host: String,
+    port: u16,
+    strict: StrictHostKeyChecking,
+    known_hosts: KnownHosts,
+    rejected: Arc<Mutex<Option<String>>>,
+    remote_forwards: Vec<Forward>,
+}
 
 #[async_trait]
 impl client::Handler for Client {
@@ -22,9 +74,73 @@ impl client::Handler for Client {
 
     async fn check_server_key(
         &mut self,
-        _server_public_key: &PublicKey,
+        server_public_key: &PublicKey,
     ) -> Result<bool, Self::Error> {
-        Ok(true)
+        if let StrictHostKeyChecking::No = self.strict {
+            return Ok(true);
+        }
+
+        match self.known_hosts.verify(&self.host, self.port, server_public_key) {
+            Verdict::Match => Ok(true),
+            Verdict::Mismatch => {
+                self.reject(format!(
+                    "host key for {} does not match a key in known_hosts",
+                    self.host
+                ));
+                Ok(false)
+            }
+            Verdict::Unknown => match self.strict {
+                StrictHostKeyChecking::AcceptNew => {
+                    if let Err(e) =
+                        self.known_hosts
+                            .append(&self.host, self.port, server_public_key)
+                    {
+                        self.reject(format!("could not record new host key: {}", e));
+                        return Ok(false);
+                    }
+                    Ok(true)
+                }
+                _ => {
+                    self.reject(format!("no host key for {} in known_hosts", self.host));
+                    Ok(false)
+                }
+            },
+        }
+    }
+
+    async fn server_channel_open_forwarded_tcpip(
+        &mut self,
+        channel: Channel<client::Msg>,
+        _connected_address: &str,
+        connected_port: u32,
+        _originator_address: &str,
+        _originator_port: u32,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        let Some(forward) = self
+            .remote_forwards
+            .iter()
+            .find(|forward| u32::from(forward.bind_port) == connected_port)
+        else {
+            return Ok(());
+        };
+
+        let host = forward.host.clone();
+        let port = forward.host_port;
+        tokio::spawn(async move {
+            if let Ok(mut local) = TcpStream::connect((host.as_str(), port)).await {
+                let mut stream = channel.into_stream();
+                let _ = copy_bidirectional(&mut local, &mut stream).await;
+            }
+        });
+
+        Ok(())
+    }
+}
+
+impl Client {
+    fn reject(&self, reason: String) {
+        *self.rejected.lock().unwrap() = Some(reason);
     }
 }
 
@@ -33,27 +149,82 @@ pub struct Session {
 }
 
 impl Session {
-    async fn connect(remote_host: &RemoteHost, key_pair: KeyPair) -> Result<Self> {
+    async fn connect(
+        remote_host: &RemoteHost,
+        authenticators: &[Authenticator],
+        strict: StrictHostKeyChecking,
+        remote_forwards: &[Forward],
+    ) -> Result<Self> {
         let config = client::Config {
             inactivity_timeout: Some(Duration::from_secs(5)),
             ..<_>::default()
         };
 
         let config = Arc::new(config);
-        let sh = Client {};
+
+        let home = home_dir().ok_or_else(|| anyhow::anyhow!("No home directory"))?;
+        let known_hosts = KnownHosts::load(home.join(".ssh/known_hosts"))?;
+        let rejected = Arc::new(Mutex::new(None));
+        let sh = Client {
+            host: remote_host.host.clone(),
+            port: remote_host.port,
+            strict,
+            known_hosts,
+            rejected: rejected.clone(),
+            remote_forwards: remote_forwards.to_vec(),
+        };
 
         let host = remote_host.host.clone();
         let username = remote_host.username.clone();
         let addr = (host, remote_host.port);
         let timeout_duration = Duration::from_secs(5);
 
-        let mut session = timeout(timeout_duration, client::connect(config, addr, sh)).await??;
+        let mut session = match timeout(timeout_duration, client::connect(config, addr, sh)).await? {
+            Ok(session) => session,
+            Err(e) => {
+                if let Some(reason) = rejected.lock().unwrap().take() {
+                    return Err(HostKeyMismatch(reason).into());
+                }
+                return Err(e.into());
+            }
+        };
 
-        let auth_res = session
-            .authenticate_publickey(username, Arc::new(key_pair))
-            .await?;
+        // Connect the agent once, not per identity.
+        let mut agent = AgentClient::connect_env().await.ok();
+
+        // Offer each candidate in turn and stop at the first the server accepts.
+        // SSH's `signature=false` query probe would let us pre-select the accepted
+        // key without signing, but russh doesn't expose it; so we offer every
+        // candidate rather than capping, otherwise a valid key sitting behind
+        // several agent/on-disk identities would never be tried.
+        let mut authenticated = false;
+        for authenticator in authenticators {
+            match authenticator {
+                Authenticator::Key(key_pair) => {
+                    if session
+                        .authenticate_publickey(&username, Arc::new(key_pair.clone()))
+                        .await?
+                    {
+                        authenticated = true;
+                        break;
+                    }
+                }
+                Authenticator::Agent(public_key) => {
+                    let Some(agent) = agent.as_mut() else {
+                        continue;
+                    };
+                    let (_, accepted) = session
+                        .authenticate_future(&username, public_key.clone(), agent)
+                        .await;
+                    if accepted? {
+                        authenticated = true;
+                        break;
+                    }
+                }
+            }
+        }
 
-        if !auth_res {
+        if !authenticated {
             anyhow::bail!("Authentication failed");
         }
 
@@ -64,16 +235,16 @@ impl Session {
         &self,
         command: &str,
         sudo: &Option<String>,
-        sudo_password: &Option<String>,
+        sudo_password: &Option<Zeroizing<String>>,
     ) -> Result<(String, String, Option<u32>, u64)> {
+        if let (Some(sudo), Some(pass)) = (sudo, sudo_password) {
+            return self.call_sudo(command, sudo, pass).await;
+        }
+
         let mut channel = self.session.channel_open_session().await?;
         let start_time = std::time::Instant::now();
 
         match (sudo, sudo_password) {
-            (Some(sudo), Some(pass)) => {
-                let command = format!("echo {} | sudo -u {} -S  {}", pass, sudo, command);
-                channel.exec(true, command.as_str()).await?;
-            }
             (Some(sudo), None) => {
                 let command = format!("sudo -u {} {}", sudo, command);
                 channel.exec(true, command.as_str()).await?;
@@ -115,6 +286,231 @@ impl Session {
         Ok((out, err, code, duration))
     }
 
+    /// Run a command under `sudo` without putting the password on the remote
+    /// command line. A pty is requested so `sudo -S` prompts on the channel; the
+    /// password is written to stdin once the prompt appears and then zeroized. The
+    /// pty is requested with `ECHO`/`ECHONL` disabled so the remote line discipline
+    /// does not echo the password back to us as captured output.
+    async fn call_sudo(
+        &self,
+        command: &str,
+        sudo: &str,
+        password: &str,
+    ) -> Result<(String, String, Option<u32>, u64)> {
+        const PROMPT: &str = "mssh-sudo-password:";
+
+        let mut channel = self.session.channel_open_session().await?;
+        let start_time = std::time::Instant::now();
+
+        let (cols, rows) = size().unwrap_or((80, 24));
+        let term = std::env::var("TERM").unwrap_or_else(|_| "xterm".to_string());
+        let modes = [(Pty::ECHO, 0), (Pty::ECHONL, 0)];
+        channel
+            .request_pty(false, &term, cols as u32, rows as u32, 0, 0, &modes)
+            .await?;
+
+        let command = format!("sudo -S -p '{}' -u {} {}", PROMPT, sudo, command);
+        channel.exec(true, command.as_str()).await?;
+
+        let mut code = None;
+        let mut out = String::new();
+        let mut err = String::new();
+        let mut sent = false;
+
+        loop {
+            let Some(msg) = channel.wait().await else {
+                break;
+            };
+            match msg {
+                ChannelMsg::Data { ref data } => {
+                    let chunk = String::from_utf8_lossy(data);
+                    if !sent && chunk.contains(PROMPT) {
+                        self.send_password(&mut channel, password).await?;
+                        sent = true;
+                    } else {
+                        out.push_str(chunk.trim());
+                    }
+                }
+
+                ChannelMsg::ExtendedData { ref data, .. } => {
+                    let chunk = String::from_utf8_lossy(data);
+                    if !sent && chunk.contains(PROMPT) {
+                        self.send_password(&mut channel, password).await?;
+                        sent = true;
+                    } else {
+                        err.push_str(chunk.trim());
+                    }
+                }
+
+                ChannelMsg::ExitStatus { exit_status } => {
+                    code = Some(exit_status);
+                }
+                _ => {}
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        let duration: u64 = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+
+        code.ok_or_else(|| anyhow::anyhow!("No exit code"))?;
+        Ok((out, err, code, duration))
+    }
+
+    async fn send_password(
+        &self,
+        channel: &mut Channel<client::Msg>,
+        password: &str,
+    ) -> Result<()> {
+        let mut buf = password.as_bytes().to_vec();
+        buf.push(b'\n');
+        let result = channel.data(&buf[..]).await;
+        buf.zeroize();
+        result?;
+        Ok(())
+    }
+
+    /// Open an interactive session: request a pty sized to the local terminal,
+    /// put it in raw mode and pump stdin/stdout until the remote shell exits,
+    /// forwarding `SIGWINCH` as window-change messages. Returns the exit code.
+    pub async fn shell(&self) -> Result<u32> {
+        let mut channel = self.session.channel_open_session().await?;
+
+        let (cols, rows) = size().unwrap_or((80, 24));
+        let term = std::env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string());
+        channel
+            .request_pty(false, &term, cols as u32, rows as u32, 0, 0, &[])
+            .await?;
+        channel.request_shell(true).await?;
+
+        enable_raw_mode()?;
+        let result = self.pump(&mut channel).await;
+        disable_raw_mode()?;
+
+        result
+    }
+
+    async fn pump(&self, channel: &mut Channel<client::Msg>) -> Result<u32> {
+        let mut stdin = tokio::io::stdin();
+        let mut stdout = tokio::io::stdout();
+        let mut buf = vec![0u8; 4096];
+        let mut winch = signal(SignalKind::window_change())?;
+        let mut code = 0;
+        let mut stdin_done = false;
+
+        loop {
+            tokio::select! {
+                read = stdin.read(&mut buf), if !stdin_done => {
+                    let n = read?;
+                    if n == 0 {
+                        // Local stdin reached EOF: half-close the channel once and stop
+                        // polling stdin so the select doesn't spin on repeated `Ok(0)`.
+                        stdin_done = true;
+                        channel.eof().await?;
+                    } else {
+                        channel.data(&buf[..n]).await?;
+                    }
+                }
+                msg = channel.wait() => {
+                    let Some(msg) = msg else {
+                        break;
+                    };
+                    match msg {
+                        ChannelMsg::Data { ref data } => {
+                            stdout.write_all(data).await?;
+                            stdout.flush().await?;
+                        }
+                        ChannelMsg::ExtendedData { ref data, .. } => {
+                            stdout.write_all(data).await?;
+                            stdout.flush().await?;
+                        }
+                        ChannelMsg::ExitStatus { exit_status } => {
+                            code = exit_status;
+                        }
+                        ChannelMsg::Eof | ChannelMsg::Close => break,
+                        _ => {}
+                    }
+                }
+                _ = winch.recv() => {
+                    let (cols, rows) = size().unwrap_or((80, 24));
+                    channel.window_change(cols as u32, rows as u32, 0, 0).await?;
+                }
+            }
+        }
+
+        Ok(code)
+    }
+
+    /// Upload a local file to `remote` over an sftp channel. Returns the number of
+    /// bytes written and the elapsed time in milliseconds.
+    pub async fn push(&self, local: &Path, remote: &str) -> Result<(u64, u64)> {
+        let start_time = std::time::Instant::now();
+        let sftp = self.sftp().await?;
+
+        let contents = tokio::fs::read(local).await?;
+        let mut file = sftp.create(remote).await?;
+        file.write_all(&contents).await?;
+        file.flush().await?;
+
+        Ok((contents.len() as u64, millis(start_time)))
+    }
+
+    /// Download `remote` over an sftp channel and write it to `local`. Returns the
+    /// number of bytes read and the elapsed time in milliseconds.
+    pub async fn pull(&self, remote: &str, local: &Path) -> Result<(u64, u64)> {
+        let start_time = std::time::Instant::now();
+        let sftp = self.sftp().await?;
+
+        let mut file = sftp.open(remote).await?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await?;
+        tokio::fs::write(local, &contents).await?;
+
+        Ok((contents.len() as u64, millis(start_time)))
+    }
+
+    async fn sftp(&self) -> Result<SftpSession> {
+        let channel = self.session.channel_open_session().await?;
+        channel.request_subsystem(true, "sftp").await?;
+        let sftp = SftpSession::new(channel.into_stream()).await?;
+
+        Ok(sftp)
+    }
+
+    /// Bind a local listener and tunnel each accepted connection to `host:host_port`
+    /// on the remote side via a `direct-tcpip` channel. Loops until accepting a
+    /// connection or opening its channel fails.
+    pub async fn forward_local(&self, forward: &Forward) -> Result<()> {
+        let listener = TcpListener::bind((forward.bind_host.as_str(), forward.bind_port)).await?;
+
+        loop {
+            let (mut socket, peer) = listener.accept().await?;
+            let channel = self
+                .session
+                .channel_open_direct_tcpip(
+                    forward.host.clone(),
+                    u32::from(forward.host_port),
+                    peer.ip().to_string(),
+                    u32::from(peer.port()),
+                )
+                .await?;
+
+            tokio::spawn(async move {
+                let mut stream = channel.into_stream();
+                let _ = copy_bidirectional(&mut socket, &mut stream).await;
+            });
+        }
+    }
+
+    /// Ask the server to listen on `bind_host:bind_port`; forwarded connections are
+    /// dialed back to the local target by the handler's forwarded-channel callback.
+    pub async fn forward_remote(&self, forward: &Forward) -> Result<()> {
+        self.session
+            .tcpip_forward(forward.bind_host.clone(), u32::from(forward.bind_port))
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn close(&mut self) -> Result<()> {
         self.session
             .disconnect(Disconnect::ByApplication, "", "English")
@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferDirection {
+    Push,
+    Pull,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    pub direction: TransferDirection,
+    pub local: PathBuf,
+    pub remote: String,
+}
+
+pub fn parse_push(spec: &str) -> Result<Transfer> {
+    let (local, remote) = spec
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Invalid push spec, expected LOCAL:REMOTE"))?;
+
+    Ok(Transfer {
+        direction: TransferDirection::Push,
+        local: PathBuf::from(local),
+        remote: remote.to_string(),
+    })
+}
+
+pub fn parse_pull(spec: &str) -> Result<Transfer> {
+    let (remote, local) = spec
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Invalid pull spec, expected REMOTE:LOCAL"))?;
+
+    Ok(Transfer {
+        direction: TransferDirection::Pull,
+        local: PathBuf::from(local),
+        remote: remote.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_push() {
+        let result = parse_push("./local.txt:/tmp/remote.txt").unwrap();
+        assert_eq!(result.direction, TransferDirection::Push);
+        assert_eq!(result.local, PathBuf::from("./local.txt"));
+        assert_eq!(result.remote, "/tmp/remote.txt");
+    }
+
+    #[test]
+    fn test_parse_pull() {
+        let result = parse_pull("/tmp/remote.txt:./local.txt").unwrap();
+        assert_eq!(result.direction, TransferDirection::Pull);
+        assert_eq!(result.remote, "/tmp/remote.txt");
+        assert_eq!(result.local, PathBuf::from("./local.txt"));
+    }
+
+    #[test]
+    fn test_parse_push_invalid() {
+        assert!(parse_push("no-colon").is_err());
+    }
+}